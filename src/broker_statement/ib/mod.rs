@@ -0,0 +1,512 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use std::iter::Iterator;
+
+use num_traits::identities::Zero;
+
+use chrono::Duration;
+
+use csv::{self, StringRecord};
+
+use core::{EmptyResult, GenericResult};
+use currency::{Cash, CashAssets, MultiCurrencyCashAccount};
+use broker_statement::{BrokerStatement, BrokerStatementBuilder};
+use broker_statement::trade::FifoLotQueue;
+use broker_statement::payment::{Dividend, Interest};
+use types::Date;
+use util;
+
+mod xml;
+
+pub struct IbStatementParser {
+    pub(super) statement: BrokerStatementBuilder,
+    pub(super) tickers: HashMap<String, String>,
+    pub(super) taxes: HashMap<(Date, String), Cash>,
+    pub(super) trades: HashMap<String, FifoLotQueue>,
+    pub(super) period: Option<(Date, Date)>,
+    pub(super) base_currency: Option<String>,
+}
+
+impl IbStatementParser {
+    pub fn new() -> IbStatementParser {
+        IbStatementParser {
+            statement: BrokerStatementBuilder::new(),
+            tickers: HashMap::new(),
+            taxes: HashMap::new(),
+            trades: HashMap::new(),
+            period: None,
+            base_currency: None,
+        }
+    }
+
+    pub fn parse(self, path: &str) -> GenericResult<BrokerStatement> {
+        if is_flex_xml_statement(path)? {
+            return xml::IbFlexParser::new().parse(self, path);
+        }
+
+        self.parse_csv(path)
+    }
+
+    fn parse_csv(mut self, path: &str) -> GenericResult<BrokerStatement> {
+        let mut reader = csv::ReaderBuilder::new()
+            .has_headers(false)
+            .flexible(true)
+            .from_path(path)?;
+
+        let mut records = reader.records();
+        let mut state = Some(State::None);
+
+        'state: loop {
+            match state.take().unwrap() {
+                State::None => {
+                    match records.next() {
+                        Some(result) => state = Some(State::Record(result?)),
+                        None => break,
+                    };
+                }
+                State::Record(record) => {
+                    if record.len() < 2 {
+                        return Err!("Invalid record: {}", format_record(&record));
+                    }
+
+                    if record.get(1).unwrap() == "Header" {
+                        state = Some(State::Header(record));
+                    } else if record.get(1).unwrap() == "" {
+                        trace!("Headerless record: {}.", format_record(&record));
+                        state = Some(State::None);
+                    } else {
+                        return Err!("Invalid record: {}", format_record(&record));
+                    }
+                },
+                State::Header(record) => {
+                    let (name, fields) = parse_header(&record)?;
+
+                    let parser: Box<RecordParser> = match name {
+                        "Statement" => Box::new(StatementInfoParser {}),
+                        "Account Information" => Box::new(StatementInfoParser {}),
+                        "Net Asset Value" => Box::new(NetAssetValueParser {}),
+                        "Withholding Tax" => Box::new(WithholdingTaxParser {}),
+                        "Deposits & Withdrawals" => Box::new(DepositsParser {}),
+                        "Interest" => Box::new(InterestParser {}),
+                        "Dividends" => Box::new(DividendParser {}),
+                        "Trades" => Box::new(TradesParser {}),
+                        "Financial Instrument Information" => Box::new(FinancialInstrumentInformationParser {}),
+                        _ => Box::new(UnknownRecordParser {}),
+                    };
+
+                    let data_types = parser.data_types();
+
+                    while let Some(result) = records.next() {
+                        let record = result?;
+
+                        if record.len() < 2 {
+                            return Err!("Invalid record: {}", format_record(&record));
+                        }
+
+                        if record.get(0).unwrap() != name {
+                            state = Some(State::Record(record));
+                            continue 'state;
+                        } else if record.get(1).unwrap() == "Header" {
+                            state = Some(State::Header(record));
+                            continue 'state;
+                        }
+
+                        if let Some(data_types) = data_types {
+                            if !data_types.contains(&record.get(1).unwrap()) {
+                                return Err!("Invalid data record type: {}", format_record(&record));
+                            }
+                        }
+
+                        parser.parse(&mut self, &Record {
+                            name: name,
+                            fields: &fields,
+                            values: &record,
+                        }).map_err(|e| format!(
+                            "Failed to parse ({}) record: {}", format_record(&record), e
+                        ))?;
+                    }
+
+                    break;
+                }
+            }
+        }
+
+        for (symbol, lots) in self.trades.drain() {
+            self.statement.open_positions.insert(symbol, lots.into_open_lots());
+        }
+        self.statement.instrument_names = self.tickers;
+
+        for dividend in &mut self.statement.dividends {
+            let tax_id = (dividend.date, dividend.description.clone());
+            dividend.paid_tax = self.taxes.remove(&tax_id);
+        }
+
+        Ok(self.statement.get().map_err(|e| format!("Invalid statement: {}", e))?)
+    }
+}
+
+enum State {
+    None,
+    Record(StringRecord),
+    Header(StringRecord),
+}
+
+struct Record<'a> {
+    name: &'a str,
+    fields: &'a Vec<&'a str>,
+    values: &'a StringRecord,
+}
+
+impl<'a> Record<'a> {
+    fn get_value(&self, field: &str) -> GenericResult<&str> {
+        if let Some(index) = self.fields.iter().position(|other: &&str| *other == field) {
+            if let Some(value) = self.values.get(index + 2) {
+                return Ok(value);
+            }
+        }
+
+        Err!("{:?} record doesn't have {:?} field", self.name, field)
+    }
+}
+
+fn parse_header(record: &StringRecord) -> GenericResult<(&str, Vec<&str>)> {
+    let name = record.get(0).unwrap();
+    let fields = record.iter().skip(2).collect::<Vec<_>>();
+    trace!("Header: {}: {}.", name, format_record(fields.iter().map(|field: &&str| *field)));
+    Ok((name, fields))
+}
+
+trait RecordParser {
+    fn data_types(&self) -> Option<&'static [&'static str]> { Some(&["Data"]) }
+    fn parse(&self, parser: &mut IbStatementParser, record: &Record) -> EmptyResult;
+}
+
+struct StatementInfoParser {}
+
+impl RecordParser for StatementInfoParser {
+    fn parse(&self, parser: &mut IbStatementParser, record: &Record) -> EmptyResult {
+        match record.get_value("Field Name")? {
+            "Period" => {
+                let period = parse_period(record.get_value("Field Value")?)?;
+                parser.period = Some(period);
+                parser.statement.set_period(period)?;
+            },
+            "Base Currency" => {
+                parser.base_currency = Some(record.get_value("Field Value")?.to_owned());
+            },
+            _ => {},
+        }
+
+        Ok(())
+    }
+}
+
+struct NetAssetValueParser {}
+
+impl RecordParser for NetAssetValueParser {
+    fn parse(&self, parser: &mut IbStatementParser, record: &Record) -> EmptyResult {
+        let asset_class = match record.get_value("Asset Class") {
+            // FIXME: We should be able to handle data with different headers somehow
+            Err(_) => return Ok(()),
+            Ok(asset_class) => asset_class,
+        };
+
+        // `historical_cash_assets` exists to validate a pure cash balance against
+        // `CashAssetsComparator`; baking the market value of open stock positions into it would
+        // show a permanent discrepancy equal to the account's equity value.
+        if asset_class == "Cash" {
+            let currency = match record.get_value("Currency") {
+                Ok(currency) => currency.to_owned(),
+                Err(_) => parser.base_currency.clone().ok_or_else(|| format!(
+                    "Unable to determine the currency of a Net Asset Value record: \
+                     the statement's base currency is unknown"))?,
+            };
+
+            let amount = Cash::new_from_string(&currency, record.get_value("Current Total")?)?;
+            let (_, period_end) = parser.period.ok_or_else(|| format!(
+                "Got a Net Asset Value record before the statement's period is known"))?;
+            let date = period_end - Duration::days(1);
+
+            parser.statement.historical_cash_assets.entry(date)
+                .or_insert_with(MultiCurrencyCashAccount::new)
+                .deposit(amount);
+        }
+
+        Ok(())
+    }
+}
+
+struct WithholdingTaxParser {}
+
+impl RecordParser for WithholdingTaxParser {
+    fn parse(&self, parser: &mut IbStatementParser, record: &Record) -> EmptyResult {
+        let currency = record.get_value("Currency")?;
+        if currency == "Total" {
+            return Ok(());
+        }
+
+        let date = parse_date(record.get_value("Date")?)?;
+        let description = record.get_value("Description")?.to_owned();
+        let amount = Cash::new_from_string(currency, record.get_value("Amount")?)?;
+
+        record_withholding_tax(parser, date, description, amount)
+    }
+}
+
+// Tax amount is represented as a negative number.
+// Positive number is used to cancel a previous tax payment and usually followed by another
+// negative number.
+//
+// Shared between the CSV and Flex XML parsers since both statement formats use the same
+// cancel-and-reissue convention for withholding tax corrections.
+pub(super) fn record_withholding_tax(
+    parser: &mut IbStatementParser, date: Date, description: String, mut tax: Cash,
+) -> EmptyResult {
+    let tax_id = (date, description.clone());
+
+    if tax.amount.is_zero() {
+        return Err!("Invalid withholding tax: {}", tax.amount);
+    } else if tax.amount.is_sign_positive() {
+        return match parser.taxes.remove(&tax_id) {
+            Some(cancelled_tax) if cancelled_tax == tax => Ok(()),
+            _ => Err!("Invalid withholding tax: {}", tax.amount),
+        }
+    }
+
+    tax.amount = -tax.amount;
+
+    if let Some(_) = parser.taxes.insert(tax_id, tax) {
+        return Err!("Got a duplicate withholding tax: {} / {:?}", date, description);
+    }
+
+    Ok(())
+}
+
+struct DepositsParser {}
+
+impl RecordParser for DepositsParser {
+    fn parse(&self, parser: &mut IbStatementParser, record: &Record) -> EmptyResult {
+        let currency = record.get_value("Currency")?;
+        if currency.starts_with("Total") {
+            return Ok(());
+        }
+
+        // FIXME: Distinguish withdrawals from deposits
+        let date = parse_date(record.get_value("Settle Date")?)?;
+        let amount = Cash::new_from_string_positive(currency, record.get_value("Amount")?)?;
+
+        parser.statement.deposits.push(CashAssets::new_from_cash(date, amount));
+
+        Ok(())
+    }
+}
+
+struct InterestParser {}
+
+impl RecordParser for InterestParser {
+    fn parse(&self, parser: &mut IbStatementParser, record: &Record) -> EmptyResult {
+        let currency = record.get_value("Currency")?;
+        if currency.starts_with("Total") {
+            return Ok(());
+        }
+
+        let date = parse_date(record.get_value("Date")?)?;
+        let description = record.get_value("Description")?.to_owned();
+        let amount = Cash::new_from_string(currency, record.get_value("Amount")?)?;
+
+        parser.statement.interest.push(Interest { date, amount, description });
+
+        Ok(())
+    }
+}
+
+struct DividendParser {}
+
+impl RecordParser for DividendParser {
+    fn parse(&self, parser: &mut IbStatementParser, record: &Record) -> EmptyResult {
+        let currency = record.get_value("Currency")?;
+        if currency.starts_with("Total") {
+            return Ok(());
+        }
+
+        let date = parse_date(record.get_value("Date")?)?;
+        let description = record.get_value("Description")?.to_owned();
+        let amount = Cash::new_from_string(currency, record.get_value("Amount")?)?;
+        let symbol = parse_dividend_symbol(&description);
+
+        // The matching Withholding Tax entry for this payment may appear in a section before or
+        // after this one, so pairing happens once the whole statement has been read (see the end
+        // of `IbStatementParser::parse_csv`).
+        parser.statement.dividends.push(Dividend {
+            date, symbol, amount, description,
+            paid_tax: None,
+        });
+
+        Ok(())
+    }
+}
+
+fn parse_dividend_symbol(description: &str) -> Option<String> {
+    let symbol = description.split('(').next().unwrap_or("").trim();
+    if symbol.is_empty() {
+        None
+    } else {
+        Some(symbol.to_owned())
+    }
+}
+
+struct TradesParser {}
+
+impl RecordParser for TradesParser {
+    fn parse(&self, parser: &mut IbStatementParser, record: &Record) -> EmptyResult {
+        // Each execution is reported as an "Order" row plus, for sells, one or more "ClosedLot"
+        // rows detailing which specific lots it closed. We only need the "Order" rows: our own
+        // FIFO queue recomputes lot consumption independently, and "SummaryTotal" rows are just
+        // per-symbol/per-asset-class subtotals.
+        if record.get_value("DataDiscriminator")? != "Order" {
+            return Ok(());
+        }
+
+        // The Trades section also reports Forex conversions as "Order" rows alongside actual
+        // stock trades. Only the latter belong in the per-symbol FIFO lot queue.
+        if record.get_value("Asset Category")? != "Stocks" {
+            return Ok(());
+        }
+
+        let symbol = record.get_value("Symbol")?.to_owned();
+        let currency = record.get_value("Currency")?;
+
+        // The Trades section's "Date/Time" column is the execution date followed by a time
+        // (e.g. "2018-06-22, 10:30:00"): we only need the date part.
+        let date = parse_date(parse_trade_date_time(record.get_value("Date/Time")?))?;
+
+        let quantity = util::parse_decimal(record.get_value("Quantity")?)?;
+        let price = Cash::new_from_string(currency, record.get_value("T. Price")?)?;
+
+        let mut commission = Cash::new_from_string(currency, record.get_value("Comm/Fee")?)?;
+        commission.amount = commission.amount.abs();
+
+        let lots = parser.trades.entry(symbol.clone())
+            .or_insert_with(|| FifoLotQueue::new(&symbol));
+
+        let gain = if quantity.is_sign_positive() {
+            lots.buy(date, quantity, price, commission)?
+        } else {
+            lots.sell(date, -quantity, price, commission)?
+        };
+
+        if let Some(gain) = gain {
+            parser.statement.realized_gains.push(gain);
+        }
+
+        Ok(())
+    }
+}
+
+struct FinancialInstrumentInformationParser {
+}
+
+impl RecordParser for FinancialInstrumentInformationParser {
+    fn parse(&self, parser: &mut IbStatementParser, record: &Record) -> EmptyResult {
+        parser.tickers.insert(
+            record.get_value("Symbol")?.to_owned(),
+            record.get_value("Description")?.to_owned(),
+        );
+        Ok(())
+    }
+}
+
+struct UnknownRecordParser {}
+
+impl RecordParser for UnknownRecordParser {
+    fn data_types(&self) -> Option<&'static [&'static str]> {
+        None
+    }
+
+    fn parse(&self, _parser: &mut IbStatementParser, record: &Record) -> EmptyResult {
+        if false {
+            trace!("Data: {}.", format_record(record.values.iter().skip(1)));
+        }
+        Ok(())
+    }
+}
+
+fn is_flex_xml_statement(path: &str) -> GenericResult<bool> {
+    let mut buffer = [0; 256];
+    let size = File::open(path)?.read(&mut buffer)?;
+    Ok(String::from_utf8_lossy(&buffer[..size]).trim_start().starts_with('<'))
+}
+
+fn format_record<'a, I>(iter: I) -> String
+    where I: IntoIterator<Item = &'a str> {
+
+    iter.into_iter()
+        .map(|value| format!("{:?}", value))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+// Brokers vary in the date formats their statements use (ISO dates, "Month Day, Year", dd/mm/yyyy,
+// ...), so record dates are parsed with the tokenizing fuzzy parser instead of a single fixed
+// format. Month names are matched against English names and abbreviations only, and when both
+// day/month numbers are <= 12 the first one is taken as the month (mm/dd).
+fn parse_date(date: &str) -> GenericResult<Date> {
+    util::parse_date_fuzzy(date, false)
+}
+
+// Strips a trailing time component (e.g. "2018-06-22, 10:30:00" -> "2018-06-22") so the result
+// can be handed to `parse_date`, which only understands a bare date.
+fn parse_trade_date_time(date_time: &str) -> &str {
+    date_time.split(',').next().unwrap_or(date_time).trim()
+}
+
+fn parse_period(period: &str) -> GenericResult<(Date, Date)> {
+    let dates = period.split(" - ").collect::<Vec<_>>();
+
+    return Ok(match dates.len() {
+        1 => {
+            let date = parse_date(dates[0])?;
+            (date, date + Duration::days(1))
+        },
+        2 => {
+            let start = parse_date(dates[0])?;
+            let end = parse_date(dates[1])?;
+
+            if start > end {
+                return Err!("Invalid period: {} - {}", start, end);
+            }
+
+            (start, end + Duration::days(1))
+        },
+        _ => return Err!("Invalid date: {:?}", period),
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn date_parsing() {
+        assert_eq!(parse_date("2018-06-22").unwrap(), date!(22, 6, 2018));
+    }
+
+    #[test]
+    fn trade_date_time_parsing() {
+        assert_eq!(parse_trade_date_time("2018-06-22, 10:30:00"), "2018-06-22");
+        assert_eq!(parse_trade_date_time("2018-06-22"), "2018-06-22");
+    }
+
+    #[test]
+    fn period_parsing() {
+        assert_eq!(parse_period("October 1, 2018").unwrap(),
+                   (date!(1, 10, 2018), date!(2, 10, 2018)));
+
+        assert_eq!(parse_period("September 30, 2018").unwrap(),
+                   (date!(30, 9, 2018), date!(1, 10, 2018)));
+
+        assert_eq!(parse_period("May 21, 2018 - September 28, 2018").unwrap(),
+                   (date!(21, 5, 2018), date!(29, 9, 2018)));
+    }
+}
\ No newline at end of file
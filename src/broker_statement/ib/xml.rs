@@ -0,0 +1,224 @@
+use std::fs;
+
+use serde::Deserialize;
+
+use chrono::Duration;
+
+use core::{EmptyResult, GenericResult};
+use currency::{Cash, CashAssets};
+use broker_statement::BrokerStatement;
+use types::Date;
+use util;
+
+use super::{IbStatementParser, record_withholding_tax};
+
+// Interactive Brokers also exports "Flex Query" reports as a single structured XML document
+// instead of the line-oriented CSV Activity Statement. It's far more stable across account
+// configurations than the CSV headers, so we map it onto the same `BrokerStatementBuilder` the
+// CSV parser fills in.
+//
+// Known gap: besides the statement period and `SecuritiesInfo` (instrument names), only the
+// `CashTransactions` section is mapped so far (deposits/withdrawals and withholding tax). Trades, Net Asset Value and Interest/Dividend cash transactions are not yet
+// read from Flex XML, so a statement parsed from this format is incomplete relative to the same
+// statement parsed from CSV. `parse_cash_transaction` warns when it skips a transaction type for
+// this reason; extend it (and add the `Trades`/`NetAssetValue` sections here) as each one is
+// brought up to parity with the CSV path.
+pub struct IbFlexParser {}
+
+impl IbFlexParser {
+    pub fn new() -> IbFlexParser {
+        IbFlexParser {}
+    }
+
+    pub fn parse(&self, mut parser: IbStatementParser, path: &str) -> GenericResult<BrokerStatement> {
+        let data = fs::read_to_string(path)?;
+
+        let response: FlexQueryResponse = quick_xml::de::from_str(&data)
+            .map_err(|e| format!("Failed to parse Flex Query XML: {}", e))?;
+
+        for statement in &response.flex_statements.statement {
+            let start = parse_flex_date(&statement.from_date)?;
+            let end = parse_flex_date(&statement.to_date)?;
+            parser.statement.set_period((start, end + Duration::days(1)))?;
+
+            for info in &statement.securities_info.security_info {
+                parser.tickers.insert(info.symbol.clone(), info.description.clone());
+            }
+
+            for transaction in &statement.cash_transactions.cash_transaction {
+                self.parse_cash_transaction(&mut parser, transaction)?;
+            }
+        }
+
+        // Without this, tickers collected above from `SecuritiesInfo` never reach the built
+        // statement: `instrument_names` is what `ledger::write_journal` reads, and the CSV path
+        // makes the same copy-over after its `FinancialInstrumentInformationParser` fills `tickers`
+        // (see `parses_deposits_and_instrument_names_from_flex_xml`).
+        parser.statement.instrument_names = parser.tickers;
+
+        Ok(parser.statement.get().map_err(|e| format!("Invalid statement: {}", e))?)
+    }
+
+    fn parse_cash_transaction(&self, parser: &mut IbStatementParser, transaction: &CashTransaction) -> EmptyResult {
+        let date = parse_flex_date(&transaction.date_time)?;
+
+        match transaction.transaction_type.as_str() {
+            "Deposits/Withdrawals" => {
+                let amount = Cash::new_from_string_positive(&transaction.currency, &transaction.amount)?;
+                parser.statement.deposits.push(CashAssets::new_from_cash(date, amount));
+            },
+            "Withholding Tax" => {
+                let amount = Cash::new_from_string(&transaction.currency, &transaction.amount)?;
+                record_withholding_tax(parser, date, transaction.description.clone(), amount)?;
+            },
+            other => {
+                // See the "Known gap" note on `IbFlexParser` above: this is the tracked, expected
+                // gap, not a schema we failed to anticipate, so warn rather than erroring out.
+                warn!("Skipping unsupported Flex Query cash transaction type: {:?}.", other);
+            },
+        }
+
+        Ok(())
+    }
+}
+
+fn parse_flex_date(date: &str) -> GenericResult<Date> {
+    util::parse_date(date, "%Y%m%d")
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename = "FlexQueryResponse")]
+struct FlexQueryResponse {
+    #[serde(rename = "FlexStatements")]
+    flex_statements: FlexStatements,
+}
+
+#[derive(Debug, Deserialize)]
+struct FlexStatements {
+    #[serde(rename = "FlexStatement", default)]
+    statement: Vec<FlexStatement>,
+}
+
+#[derive(Debug, Deserialize)]
+struct FlexStatement {
+    #[serde(rename = "@fromDate")]
+    from_date: String,
+    #[serde(rename = "@toDate")]
+    to_date: String,
+    #[serde(rename = "CashTransactions", default)]
+    cash_transactions: CashTransactions,
+    #[serde(rename = "SecuritiesInfo", default)]
+    securities_info: SecuritiesInfo,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct CashTransactions {
+    #[serde(rename = "CashTransaction", default)]
+    cash_transaction: Vec<CashTransaction>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CashTransaction {
+    #[serde(rename = "@currency")]
+    currency: String,
+    #[serde(rename = "@dateTime")]
+    date_time: String,
+    #[serde(rename = "@amount")]
+    amount: String,
+    #[serde(rename = "@type")]
+    transaction_type: String,
+    #[serde(rename = "@description", default)]
+    description: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct SecuritiesInfo {
+    #[serde(rename = "SecurityInfo", default)]
+    security_info: Vec<SecurityInfo>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SecurityInfo {
+    #[serde(rename = "@symbol")]
+    symbol: String,
+    #[serde(rename = "@description")]
+    description: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    const SAMPLE: &str = r#"
+        <FlexQueryResponse>
+            <FlexStatements>
+                <FlexStatement fromDate="20180601" toDate="20180630">
+                    <SecuritiesInfo>
+                        <SecurityInfo symbol="AAA" description="Some Company"/>
+                    </SecuritiesInfo>
+                    <CashTransactions>
+                        <CashTransaction currency="USD" dateTime="20180605" amount="1000" type="Deposits/Withdrawals" description="Deposit"/>
+                        <CashTransaction currency="USD" dateTime="20180610" amount="-15" type="Withholding Tax" description="AAA(US0000000000) Cash Dividend"/>
+                        <CashTransaction currency="USD" dateTime="20180612" amount="7" type="Broker Interest Received" description="Interest"/>
+                    </CashTransactions>
+                </FlexStatement>
+            </FlexStatements>
+        </FlexQueryResponse>
+    "#;
+
+    // `IbFlexParser::parse()` only reads from a path, so exercise it end to end through a
+    // throwaway file rather than mocking the filesystem.
+    fn parse_response(xml: &str) -> GenericResult<BrokerStatement> {
+        let path = format!("{}/ib-flex-test-{}.xml", std::env::temp_dir().display(), std::process::id());
+        fs::write(&path, xml).unwrap();
+        let result = IbFlexParser::new().parse(IbStatementParser::new(), &path);
+        fs::remove_file(&path).ok();
+        result
+    }
+
+    #[test]
+    fn parses_deposits_and_instrument_names_from_flex_xml() {
+        let statement = parse_response(SAMPLE).unwrap();
+
+        assert_eq!(statement.deposits.len(), 1);
+        assert_eq!(statement.deposits[0].cash, Cash::new("USD", dec!(1000)));
+
+        assert_eq!(statement.instrument_names.get("AAA"), Some(&"Some Company".to_owned()));
+    }
+
+    #[test]
+    fn records_withholding_tax_from_flex_xml() {
+        let mut parser = IbStatementParser::new();
+
+        let transaction = CashTransaction {
+            currency: "USD".to_owned(), date_time: "20180610".to_owned(), amount: "-15".to_owned(),
+            transaction_type: "Withholding Tax".to_owned(),
+            description: "AAA(US0000000000) Cash Dividend".to_owned(),
+        };
+
+        IbFlexParser::new().parse_cash_transaction(&mut parser, &transaction).unwrap();
+
+        let tax_id = (date!(10, 6, 2018), "AAA(US0000000000) Cash Dividend".to_owned());
+        assert_eq!(parser.taxes.get(&tax_id), Some(&Cash::new("USD", dec!(15))));
+    }
+
+    #[test]
+    fn unsupported_cash_transaction_types_are_skipped_without_error() {
+        let mut parser = IbStatementParser::new();
+
+        let transaction = CashTransaction {
+            currency: "USD".to_owned(), date_time: "20180612".to_owned(), amount: "7".to_owned(),
+            transaction_type: "Broker Interest Received".to_owned(),
+            description: "Interest".to_owned(),
+        };
+
+        assert!(IbFlexParser::new().parse_cash_transaction(&mut parser, &transaction).is_ok());
+    }
+
+    #[test]
+    fn flex_date_parsing() {
+        assert_eq!(parse_flex_date("20180622").unwrap(), date!(22, 6, 2018));
+    }
+}
@@ -0,0 +1,24 @@
+use currency::Cash;
+use types::Date;
+
+/// Broker interest paid on (or charged against) cash balances.
+///
+/// Kept separate from deposits/withdrawals and from trade proceeds since interest on cash is
+/// taxed differently from both.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Interest {
+    pub date: Date,
+    pub amount: Cash,
+    pub description: String,
+}
+
+/// A dividend payment, paired with the withholding tax withheld against it when a matching entry
+/// is found, so a tax report can present gross dividend, tax withheld and net per payment.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Dividend {
+    pub date: Date,
+    pub symbol: Option<String>,
+    pub amount: Cash,
+    pub description: String,
+    pub paid_tax: Option<Cash>,
+}
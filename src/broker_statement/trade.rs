@@ -0,0 +1,291 @@
+use std::collections::VecDeque;
+
+use num_traits::identities::Zero;
+use rust_decimal::Decimal;
+
+use core::GenericResult;
+use currency::Cash;
+use types::Date;
+
+/// A single open tax lot: a quantity of a symbol acquired at a given date, price and commission.
+///
+/// `quantity` is signed: positive for a long (bought) lot, negative for a short (sold-short)
+/// lot that's still open.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StockLot {
+    pub date: Date,
+    pub quantity: Decimal,
+    pub price: Cash,
+    pub commission: Cash,
+}
+
+/// Realized capital gain produced by closing (fully or partially) one or more open lots.
+///
+/// `quantity`, `price` and `proceeds` describe the closing trade itself (not just the resulting
+/// gain), so callers like the Ledger exporter can post the full transaction: the commodity
+/// posting against the closed quantity at `price`, and the cash posting against `proceeds`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RealizedGain {
+    pub date: Date,
+    pub symbol: String,
+    /// Signed: negative for shares sold (closing a long position), positive for shares bought
+    /// back (closing a short position).
+    pub quantity: Decimal,
+    pub price: Cash,
+    /// Actual cash that changed hands for the closed portion, net of its share of the
+    /// commission: positive for a sale, negative for a buy-to-cover.
+    pub proceeds: Cash,
+    pub amount: Cash,
+}
+
+/// Per-symbol FIFO queue of open tax lots used to compute realized/unrealized capital gains.
+///
+/// Buys push a new lot to the back of the queue; sells consume lots from the front, splitting
+/// the oldest lot when the sale is smaller than it. A sell that exhausts all long lots opens a
+/// short position (a lot with negative quantity), which a later buy then closes the same way.
+pub struct FifoLotQueue {
+    symbol: String,
+    lots: VecDeque<StockLot>,
+}
+
+impl FifoLotQueue {
+    pub fn new(symbol: &str) -> FifoLotQueue {
+        FifoLotQueue {
+            symbol: symbol.to_owned(),
+            lots: VecDeque::new(),
+        }
+    }
+
+    pub fn buy(&mut self, date: Date, quantity: Decimal, price: Cash, commission: Cash) -> GenericResult<Option<RealizedGain>> {
+        self.trade(date, quantity, price, commission)
+    }
+
+    pub fn sell(&mut self, date: Date, quantity: Decimal, price: Cash, commission: Cash) -> GenericResult<Option<RealizedGain>> {
+        self.trade(date, -quantity, price, commission)
+    }
+
+    pub fn open_lots(&self) -> &VecDeque<StockLot> {
+        &self.lots
+    }
+
+    pub fn into_open_lots(self) -> VecDeque<StockLot> {
+        self.lots
+    }
+
+    // `quantity` is signed here: positive means buying/covering, negative means selling/shorting.
+    fn trade(&mut self, date: Date, quantity: Decimal, price: Cash, commission: Cash) -> GenericResult<Option<RealizedGain>> {
+        if quantity.is_zero() {
+            return Err!("Got a {} trade with zero quantity", self.symbol);
+        }
+
+        let currency = price.currency;
+        let mut remaining = quantity;
+        let mut closed = Decimal::zero();
+        let mut gain = Decimal::zero();
+        let mut proceeds = Decimal::zero();
+
+        while !remaining.is_zero() {
+            let closes_existing_lot = match self.lots.front() {
+                Some(lot) if !lot.quantity.is_zero() => opposite_sign(lot.quantity, remaining),
+                _ => false,
+            };
+
+            if !closes_existing_lot {
+                let fraction = remaining.abs() / quantity.abs();
+                self.lots.push_back(StockLot {
+                    date, quantity: remaining, price,
+                    commission: Cash::new(commission.currency, commission.amount * fraction),
+                });
+                remaining = Decimal::zero();
+                continue;
+            }
+
+            let lot = self.lots.front_mut().unwrap();
+            let consumed = if remaining.abs() < lot.quantity.abs() { remaining } else { -lot.quantity };
+            let lot_fraction = consumed.abs() / lot.quantity.abs();
+            let trade_fraction = consumed.abs() / quantity.abs();
+
+            let lot_commission = lot.commission.amount * lot_fraction;
+            let trade_commission = commission.amount * trade_fraction;
+
+            // `consumed` has the sign of `remaining`, so for a sell closing a long lot this is
+            // (sell price - buy price) * quantity, and for a buy covering a short lot it's
+            // (short sale price - cover price) * quantity.
+            closed += consumed.abs();
+            gain += -consumed * (price.amount - lot.price.amount) - lot_commission - trade_commission;
+
+            // Unlike `gain`, this only reflects this trade's own commission: `lot_commission` was
+            // already paid when the lot was opened, so it isn't part of the cash that moves now
+            // (it's recognized in `gain` above, as part of the closed lot's cost).
+            proceeds += -consumed * price.amount - trade_commission;
+
+            lot.quantity += consumed;
+            lot.commission.amount -= lot_commission;
+            remaining -= consumed;
+
+            if lot.quantity.is_zero() {
+                self.lots.pop_front();
+            }
+        }
+
+        if closed.is_zero() {
+            return Ok(None);
+        }
+
+        // The closed quantity keeps the sign of the trade itself (negative for a sale) so it can
+        // be posted directly as a commodity amount change.
+        let signed_closed = if quantity.is_sign_negative() { -closed } else { closed };
+
+        Ok(Some(RealizedGain {
+            date, symbol: self.symbol.clone(), quantity: signed_closed, price,
+            proceeds: Cash::new(currency, proceeds),
+            amount: Cash::new(currency, gain),
+        }))
+    }
+}
+
+fn opposite_sign(a: Decimal, b: Decimal) -> bool {
+    a.is_sign_positive() != b.is_sign_positive()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cash(amount: Decimal) -> Cash {
+        Cash::new("USD", amount)
+    }
+
+    #[test]
+    fn full_fifo_close() {
+        let mut lots = FifoLotQueue::new("AAA");
+
+        assert!(lots.buy(date!(1, 1, 2020), dec!(10), cash(dec!(100)), cash(dec!(1))).unwrap().is_none());
+
+        let gain = lots.sell(date!(2, 1, 2020), dec!(10), cash(dec!(110)), cash(dec!(1))).unwrap().unwrap();
+        // Negative: the commodity posting reduces the holding. `proceeds` is the actual cash
+        // received ($1100 - $1 commission), distinct from `amount`, the recognized gain.
+        assert_eq!(gain.quantity, dec!(-10));
+        assert_eq!(gain.price, cash(dec!(110)));
+        assert_eq!(gain.proceeds, cash(dec!(1099)));
+        assert_eq!(gain.amount, cash(dec!(98)));
+        assert!(lots.open_lots().is_empty());
+    }
+
+    #[test]
+    fn fractional_shares() {
+        let mut lots = FifoLotQueue::new("AAA");
+
+        lots.buy(date!(1, 1, 2020), dec!(2.5), cash(dec!(100)), cash(dec!(0))).unwrap();
+        let gain = lots.sell(date!(2, 1, 2020), dec!(2.5), cash(dec!(110)), cash(dec!(0))).unwrap().unwrap();
+
+        assert_eq!(gain.amount, cash(dec!(25.0)));
+    }
+
+    #[test]
+    fn same_day_partial_fills_split_the_oldest_lot() {
+        let mut lots = FifoLotQueue::new("AAA");
+
+        lots.buy(date!(1, 1, 2020), dec!(5), cash(dec!(100)), cash(dec!(2))).unwrap();
+        lots.buy(date!(1, 1, 2020), dec!(5), cash(dec!(104)), cash(dec!(2))).unwrap();
+
+        let gain = lots.sell(date!(2, 1, 2020), dec!(7), cash(dec!(110)), cash(dec!(0))).unwrap().unwrap();
+        assert_eq!(gain.quantity, dec!(-7));
+        assert_eq!(gain.proceeds, cash(dec!(770)));
+        assert_eq!(gain.amount, cash(dec!(59.2)));
+
+        let remaining = lots.open_lots();
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].quantity, dec!(3));
+        assert_eq!(remaining[0].commission, cash(dec!(1.2)));
+    }
+
+    #[test]
+    fn short_sale_and_cover() {
+        let mut lots = FifoLotQueue::new("AAA");
+
+        // No open lots yet: the sale opens a short position instead of failing.
+        assert!(lots.sell(date!(1, 1, 2020), dec!(5), cash(dec!(50)), cash(dec!(1))).unwrap().is_none());
+        assert_eq!(lots.open_lots().front().unwrap().quantity, dec!(-5));
+
+        let gain = lots.buy(date!(2, 1, 2020), dec!(5), cash(dec!(40)), cash(dec!(1))).unwrap().unwrap();
+        // Positive: covering a short increases the holding. `proceeds` is negative: cash paid out.
+        assert_eq!(gain.quantity, dec!(5));
+        assert_eq!(gain.proceeds, cash(dec!(-201)));
+        assert_eq!(gain.amount, cash(dec!(48)));
+        assert!(lots.open_lots().is_empty());
+    }
+
+    #[test]
+    fn gain_keeps_the_lot_s_currency() {
+        let mut lots = FifoLotQueue::new("BBB");
+
+        lots.buy(date!(1, 1, 2020), dec!(1), Cash::new("EUR", dec!(10)), Cash::new("EUR", dec!(0))).unwrap();
+        let gain = lots.sell(date!(2, 1, 2020), dec!(1), Cash::new("EUR", dec!(15)), Cash::new("EUR", dec!(0))).unwrap().unwrap();
+
+        assert_eq!(gain.amount, Cash::new("EUR", dec!(5)));
+    }
+
+    #[test]
+    fn zero_quantity_trade_is_an_error() {
+        let mut lots = FifoLotQueue::new("AAA");
+        assert!(lots.buy(date!(1, 1, 2020), dec!(0), cash(dec!(100)), cash(dec!(0))).is_err());
+    }
+
+    struct ConstantPriceOracle(Option<Cash>);
+
+    impl PriceOracle for ConstantPriceOracle {
+        fn price(&self, _symbol: &str) -> Option<Cash> {
+            self.0
+        }
+    }
+
+    #[test]
+    fn unrealized_gain_values_open_lots_at_current_price() {
+        let mut lots = VecDeque::new();
+        lots.push_back(StockLot {
+            date: date!(1, 1, 2020), quantity: dec!(10), price: cash(dec!(90)), commission: cash(dec!(0)),
+        });
+
+        let oracle = ConstantPriceOracle(Some(cash(dec!(100))));
+        assert_eq!(unrealized_gain("AAA", &lots, &oracle).unwrap(), cash(dec!(100)));
+    }
+
+    #[test]
+    fn unrealized_gain_subtracts_the_lot_s_remaining_commission() {
+        let mut lots = VecDeque::new();
+        lots.push_back(StockLot {
+            date: date!(1, 1, 2020), quantity: dec!(10), price: cash(dec!(90)), commission: cash(dec!(5)),
+        });
+
+        let oracle = ConstantPriceOracle(Some(cash(dec!(100))));
+        assert_eq!(unrealized_gain("AAA", &lots, &oracle).unwrap(), cash(dec!(95)));
+    }
+
+    #[test]
+    fn unrealized_gain_without_a_price_is_an_error() {
+        let lots = VecDeque::new();
+        let oracle = ConstantPriceOracle(None);
+        assert!(unrealized_gain("AAA", &lots, &oracle).is_err());
+    }
+}
+
+/// Source of current market prices used to value open lots for unrealized gain reporting.
+pub trait PriceOracle {
+    fn price(&self, symbol: &str) -> Option<Cash>;
+}
+
+/// Values the given symbol's remaining open lots at the oracle's current price.
+pub fn unrealized_gain(symbol: &str, lots: &VecDeque<StockLot>, oracle: &PriceOracle) -> GenericResult<Cash> {
+    let price = oracle.price(symbol).ok_or_else(|| format!(
+        "Unable to value {:?}: no current price is available", symbol))?;
+
+    let mut amount = Decimal::zero();
+    for lot in lots {
+        // Mirrors `FifoLotQueue::trade()`: the lot's opening commission was never recognized as a
+        // loss anywhere else, so it has to come out of the unrealized gain here.
+        amount += lot.quantity * (price.amount - lot.price.amount) - lot.commission.amount;
+    }
+
+    Ok(Cash::new(price.currency, amount))
+}
@@ -1,4 +1,4 @@
-use std::collections::{BTreeMap, HashSet, btree_map};
+use std::collections::{BTreeMap, HashMap, HashSet, btree_map};
 
 use log::{Level, log};
 
@@ -6,10 +6,22 @@ use crate::currency::{Cash, MultiCurrencyCashAccount};
 use crate::formatting::format_date;
 use crate::types::Date;
 
+/// A single calculated-vs-actual mismatch for one currency on one reporting date, as collected
+/// by `CashAssetsComparator::compare()`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CashAssetsDiscrepancy {
+    pub date: Date,
+    pub currency: &'static str,
+    pub calculated: Cash,
+    pub actual: Cash,
+    pub difference: Cash,
+}
+
 pub struct CashAssetsComparator<'a> {
     iter: btree_map::Iter<'a, Date, MultiCurrencyCashAccount>,
     next: Option<(&'a Date, &'a MultiCurrencyCashAccount)>,
     currencies: HashSet<&'static str>,
+    discrepancies: Vec<CashAssetsDiscrepancy>,
 }
 
 impl<'a> CashAssetsComparator<'a> {
@@ -18,11 +30,18 @@ impl<'a> CashAssetsComparator<'a> {
             iter: historical.iter(),
             next: None,
             currencies: HashSet::new(),
+            discrepancies: Vec::new(),
         };
         comparator.next();
         comparator
     }
 
+    /// Discrepancies collected so far by `compare()`, for callers that want to render a report
+    /// or assert on specific mismatches instead of scraping the log.
+    pub fn discrepancies(&self) -> &[CashAssetsDiscrepancy] {
+        &self.discrepancies
+    }
+
     pub fn compare(&mut self, date: Date, calculated: &MultiCurrencyCashAccount) -> bool {
         let (&date, actual) = match self.next {
             Some(data) if *data.0 < date => {
@@ -36,7 +55,6 @@ impl<'a> CashAssetsComparator<'a> {
         self.currencies.extend(calculated.iter().map(|assets| assets.currency));
         let currencies = self.currencies();
 
-        // FIXME(konishchev): HERE
         let mut reported = false;
 
         for &currency in &currencies {
@@ -60,8 +78,12 @@ impl<'a> CashAssetsComparator<'a> {
                 log!(level, "Calculation error for {}:", format_date(date));
                 reported = true;
             }
-            log!(level, "* {} vs {} ({})",
-                 calculated_amount, actual_amount, calculated_amount.sub(actual_amount).unwrap());
+            let difference = calculated_amount.sub(actual_amount).unwrap();
+            log!(level, "* {} vs {} ({})", calculated_amount, actual_amount, difference);
+
+            self.discrepancies.push(CashAssetsDiscrepancy {
+                date, currency, calculated: calculated_amount, actual: actual_amount, difference,
+            });
         }
 
         self.next.is_none()
@@ -76,4 +98,90 @@ impl<'a> CashAssetsComparator<'a> {
     fn next(&mut self) {
         self.next = self.iter.next();
     }
+}
+
+/// Renders discrepancies as an aligned table, with the calculated/actual/difference columns
+/// right-justified per currency, for review alongside (or instead of) the log output.
+pub fn format_discrepancies_table(discrepancies: &[CashAssetsDiscrepancy]) -> String {
+    if discrepancies.is_empty() {
+        return String::new();
+    }
+
+    let date_width = discrepancies.iter()
+        .map(|discrepancy| format_date(discrepancy.date).len())
+        .max().unwrap();
+
+    let mut column_widths = HashMap::new();
+    for discrepancy in discrepancies {
+        let width: &mut usize = column_widths.entry(discrepancy.currency).or_insert(0);
+        for amount in &[discrepancy.calculated, discrepancy.actual, discrepancy.difference] {
+            *width = (*width).max(format!("{}", amount).len());
+        }
+    }
+
+    let mut table = String::new();
+
+    for discrepancy in discrepancies {
+        let width = column_widths[discrepancy.currency];
+
+        table.push_str(&format!(
+            "{:<date_width$}  {:>width$} vs {:>width$} ({:>width$})\n",
+            format_date(discrepancy.date),
+            discrepancy.calculated, discrepancy.actual, discrepancy.difference,
+            date_width = date_width, width = width,
+        ));
+    }
+
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn discrepancy(currency: &'static str, calculated: &str, actual: &str) -> CashAssetsDiscrepancy {
+        let calculated = Cash::new(currency, calculated.parse().unwrap());
+        let actual = Cash::new(currency, actual.parse().unwrap());
+        let difference = calculated.sub(actual).unwrap();
+
+        CashAssetsDiscrepancy {
+            date: date!(1, 1, 2020), currency, calculated, actual, difference,
+        }
+    }
+
+    #[test]
+    fn empty_table_is_empty_string() {
+        assert_eq!(format_discrepancies_table(&[]), "");
+    }
+
+    #[test]
+    fn table_has_one_row_per_discrepancy_right_justified_per_currency() {
+        let discrepancies = vec![
+            discrepancy("USD", "100", "99.5"),
+            discrepancy("EUR", "1000.25", "1000"),
+        ];
+
+        let table = format_discrepancies_table(&discrepancies);
+        let lines = table.lines().collect::<Vec<_>>();
+
+        assert_eq!(lines.len(), 2);
+        for line in &lines {
+            assert!(line.contains("vs"));
+        }
+        assert!(lines[0].contains("USD"));
+        assert!(lines[1].contains("EUR"));
+
+        // Within a row, the calculated, actual and difference columns are padded on the left to
+        // their currency's common width.
+        let date_width = format_date(date!(1, 1, 2020)).len();
+        for line in &lines {
+            let vs = line.find(" vs ").unwrap();
+            let open = line.rfind(" (").unwrap();
+            let columns = [&line[date_width + 2..vs], &line[vs + 4..open], &line[open + 2..line.len() - 1]];
+            for column in &columns {
+                assert_eq!(column.chars().count(), columns[0].chars().count());
+                assert!(!column.ends_with(' '));
+            }
+        }
+    }
 }
\ No newline at end of file
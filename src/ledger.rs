@@ -0,0 +1,225 @@
+use std::collections::HashMap;
+use std::io::Write;
+
+use core::EmptyResult;
+use broker_statement::BrokerStatement;
+use broker_statement::payment::{Dividend, Interest};
+use broker_statement::trade::RealizedGain;
+use currency::CashAssets;
+use formatting::format_date;
+
+/// Writes a plain-text Ledger/hledger-compatible double-entry journal for the given statement.
+///
+/// Each deposit/withdrawal, interest payment, dividend (with its withholding tax, if any) and
+/// closed trade becomes a dated transaction with balanced postings against an `Assets:IB:Cash`
+/// account (closed trades also post the commodity leg against `Assets:IB:<SYMBOL>`), so the data
+/// can flow into an existing Ledger-based bookkeeping setup.
+pub fn write_journal(statement: &BrokerStatement, writer: &mut Write) -> EmptyResult {
+    for deposit in &statement.deposits {
+        write_deposit(writer, deposit)?;
+    }
+
+    for interest in &statement.interest {
+        write_interest(writer, interest)?;
+    }
+
+    for dividend in &statement.dividends {
+        write_dividend(writer, &statement.instrument_names, dividend)?;
+    }
+
+    for gain in &statement.realized_gains {
+        write_trade(writer, &statement.instrument_names, gain)?;
+    }
+
+    Ok(())
+}
+
+fn write_deposit(writer: &mut Write, deposit: &CashAssets) -> EmptyResult {
+    let equity_account = if deposit.cash.amount.is_sign_negative() {
+        "Equity:Withdrawals"
+    } else {
+        "Equity:Deposits"
+    };
+
+    writeln!(writer, "{} Deposit", format_date(deposit.date))?;
+    writeln!(writer, "    Assets:IB:Cash              {}", deposit.cash)?;
+    writeln!(writer, "    {}", equity_account)?;
+    writeln!(writer)?;
+
+    Ok(())
+}
+
+fn write_interest(writer: &mut Write, interest: &Interest) -> EmptyResult {
+    let income_account = if interest.amount.amount.is_sign_negative() {
+        "Expenses:Interest"
+    } else {
+        "Income:Interest"
+    };
+
+    writeln!(writer, "{} Interest  ; {}", format_date(interest.date), interest.description)?;
+    writeln!(writer, "    Assets:IB:Cash              {}", interest.amount)?;
+    writeln!(writer, "    {}", income_account)?;
+    writeln!(writer)?;
+
+    Ok(())
+}
+
+fn write_dividend(writer: &mut Write, instrument_names: &HashMap<String, String>, dividend: &Dividend) -> EmptyResult {
+    let description = dividend.symbol.as_ref()
+        .and_then(|symbol| instrument_names.get(symbol))
+        .map(String::as_str)
+        .unwrap_or(&dividend.description);
+
+    let account = match &dividend.symbol {
+        Some(symbol) => format!("Income:Dividends:{}", symbol),
+        None => "Income:Dividends".to_owned(),
+    };
+
+    writeln!(writer, "{} Dividend  ; {}", format_date(dividend.date), description)?;
+
+    match dividend.paid_tax {
+        Some(tax) => {
+            let net = dividend.amount.sub(tax).unwrap();
+            writeln!(writer, "    Assets:IB:Cash              {}", net)?;
+            writeln!(writer, "    Expenses:Taxes:Withholding  {}", tax)?;
+        },
+        None => {
+            writeln!(writer, "    Assets:IB:Cash              {}", dividend.amount)?;
+        },
+    }
+
+    writeln!(writer, "    {}", account)?;
+    writeln!(writer)?;
+
+    Ok(())
+}
+
+fn write_trade(writer: &mut Write, instrument_names: &HashMap<String, String>, gain: &RealizedGain) -> EmptyResult {
+    let description = instrument_names.get(&gain.symbol)
+        .map(String::as_str)
+        .unwrap_or(&gain.symbol);
+
+    // A sale reduces the commodity holding (`gain.quantity` is negative) and credits the actual
+    // cash proceeds; a buy-to-cover does the opposite. The realized gain itself is left for
+    // Ledger to auto-balance, same as the other postings in this module.
+    let verb = if gain.quantity.is_sign_negative() { "Sell" } else { "Buy" };
+
+    writeln!(writer, "{} {} {}  ; {}", format_date(gain.date), verb, gain.symbol, description)?;
+    writeln!(writer, "    Assets:IB:{:<14}  {} {} @ {}", gain.symbol, gain.quantity, gain.symbol, gain.price)?;
+    writeln!(writer, "    Assets:IB:Cash              {}", gain.proceeds)?;
+    writeln!(writer, "    Income:CapitalGains:{}", gain.symbol)?;
+    writeln!(writer)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str;
+
+    use currency::Cash;
+
+    use super::*;
+
+    #[test]
+    fn deposit_and_withdrawal() {
+        let mut buffer = Vec::new();
+        write_deposit(&mut buffer, &CashAssets::new_from_cash(
+            date!(22, 6, 2018), Cash::new("USD", dec!(1000)))).unwrap();
+        write_deposit(&mut buffer, &CashAssets::new_from_cash(
+            date!(23, 6, 2018), Cash::new("USD", dec!(-500)))).unwrap();
+
+        let journal = str::from_utf8(&buffer).unwrap();
+        assert!(journal.contains("Equity:Deposits"));
+        assert!(journal.contains("Equity:Withdrawals"));
+    }
+
+    #[test]
+    fn interest_income_and_expense() {
+        let mut buffer = Vec::new();
+        write_interest(&mut buffer, &Interest {
+            date: date!(1, 1, 2019), amount: Cash::new("USD", dec!(10)), description: "Credit".to_owned(),
+        }).unwrap();
+        write_interest(&mut buffer, &Interest {
+            date: date!(2, 1, 2019), amount: Cash::new("USD", dec!(-10)), description: "Debit".to_owned(),
+        }).unwrap();
+
+        let journal = str::from_utf8(&buffer).unwrap();
+        assert!(journal.contains("Income:Interest"));
+        assert!(journal.contains("Expenses:Interest"));
+    }
+
+    #[test]
+    fn dividend_with_withholding_tax() {
+        let mut instrument_names = HashMap::new();
+        instrument_names.insert("AAA".to_owned(), "Some Company".to_owned());
+
+        let mut buffer = Vec::new();
+        write_dividend(&mut buffer, &instrument_names, &Dividend {
+            date: date!(1, 1, 2019),
+            symbol: Some("AAA".to_owned()),
+            amount: Cash::new("USD", dec!(100)),
+            description: "AAA(US0000000000) Cash Dividend".to_owned(),
+            paid_tax: Some(Cash::new("USD", dec!(10))),
+        }).unwrap();
+
+        let journal = str::from_utf8(&buffer).unwrap();
+        assert!(journal.contains("Some Company"));
+        assert!(journal.contains("Income:Dividends:AAA"));
+        assert!(journal.contains("Expenses:Taxes:Withholding"));
+        assert!(journal.contains("90"));
+    }
+
+    #[test]
+    fn dividend_without_withholding_tax() {
+        let mut buffer = Vec::new();
+        write_dividend(&mut buffer, &HashMap::new(), &Dividend {
+            date: date!(1, 1, 2019),
+            symbol: None,
+            amount: Cash::new("USD", dec!(100)),
+            description: "Some payment".to_owned(),
+            paid_tax: None,
+        }).unwrap();
+
+        let journal = str::from_utf8(&buffer).unwrap();
+        assert!(!journal.contains("Expenses:Taxes:Withholding"));
+        assert!(journal.contains("Income:Dividends"));
+    }
+
+    #[test]
+    fn sell_posts_the_commodity_and_actual_cash_proceeds() {
+        let mut instrument_names = HashMap::new();
+        instrument_names.insert("AAA".to_owned(), "Some Company".to_owned());
+
+        let mut buffer = Vec::new();
+        write_trade(&mut buffer, &instrument_names, &RealizedGain {
+            date: date!(2, 1, 2020), symbol: "AAA".to_owned(),
+            quantity: dec!(-10), price: Cash::new("USD", dec!(110)),
+            proceeds: Cash::new("USD", dec!(1099)), amount: Cash::new("USD", dec!(98)),
+        }).unwrap();
+
+        let journal = str::from_utf8(&buffer).unwrap();
+        assert!(journal.contains("Sell AAA"));
+        assert!(journal.contains("Some Company"));
+        assert!(journal.contains("-10 AAA @"));
+        assert!(journal.contains("1099"));
+        assert!(journal.contains("Income:CapitalGains:AAA"));
+        // The gain itself is left for Ledger to auto-balance, not printed explicitly.
+        assert!(!journal.lines().any(|line| line.contains("Income:CapitalGains:AAA") && line.contains("98")));
+    }
+
+    #[test]
+    fn buy_to_cover_posts_a_negative_cash_proceeds() {
+        let mut buffer = Vec::new();
+        write_trade(&mut buffer, &HashMap::new(), &RealizedGain {
+            date: date!(2, 1, 2020), symbol: "AAA".to_owned(),
+            quantity: dec!(5), price: Cash::new("USD", dec!(40)),
+            proceeds: Cash::new("USD", dec!(-201)), amount: Cash::new("USD", dec!(48)),
+        }).unwrap();
+
+        let journal = str::from_utf8(&buffer).unwrap();
+        assert!(journal.contains("Buy AAA"));
+        assert!(journal.contains("5 AAA @"));
+        assert!(journal.contains("-201"));
+    }
+}
@@ -0,0 +1,186 @@
+use std::str::FromStr;
+
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+
+use core::GenericResult;
+use types::Date;
+
+pub fn parse_date(date: &str, format: &str) -> GenericResult<Date> {
+    Ok(NaiveDate::parse_from_str(date, format).map_err(|e| format!(
+        "Invalid date {:?}: {}", date, e))?)
+}
+
+pub fn parse_decimal(value: &str) -> GenericResult<Decimal> {
+    Ok(Decimal::from_str(value.trim()).map_err(|_| format!(
+        "Invalid decimal value: {:?}", value))?)
+}
+
+/// A dateutil-style fuzzy date parser for brokers/locales that don't stick to a single fixed
+/// format: scans the string into alphabetic, numeric and separator runs, takes a 4-digit numeric
+/// run as the year, matches alphabetic runs against month names/abbreviations, and resolves the
+/// remaining one or two numeric runs into day/month.
+///
+/// `prefer_day_first` only matters when both remaining numbers are ambiguous (<= 12): it picks
+/// `dd/mm` over `mm/dd` when set. A number greater than 12 is unambiguously the day, regardless
+/// of this setting. Ambiguous or contradictory input is reported as an error instead of being
+/// silently guessed.
+pub fn parse_date_fuzzy(date: &str, prefer_day_first: bool) -> GenericResult<Date> {
+    let mut year = None;
+    let mut month = None;
+    let mut numbers = Vec::new();
+
+    for token in tokenize(date) {
+        match token {
+            Token::Alpha(word) => {
+                let number = month_number(&word).ok_or_else(|| format!(
+                    "Unable to parse {:?}: {:?} isn't a known month name", date, word))?;
+
+                if month.replace(number).is_some() {
+                    return Err!("Unable to parse {:?}: got more than one month name", date);
+                }
+            },
+            Token::Numeric(digits) => {
+                let value: i32 = digits.parse()?;
+
+                if digits.len() == 4 {
+                    if year.replace(value).is_some() {
+                        return Err!("Unable to parse {:?}: got more than one 4-digit number", date);
+                    }
+                } else {
+                    numbers.push(value);
+                }
+            },
+            Token::Separator => {},
+        }
+    }
+
+    let year = year.ok_or_else(|| format!("Unable to parse {:?}: no 4-digit year found", date))?;
+
+    let (day, month) = match month {
+        Some(month) => {
+            if numbers.len() != 1 {
+                return Err!(
+                    "Unable to parse {:?}: expected exactly one day number alongside the month name", date);
+            }
+            (numbers[0], month)
+        },
+        None => {
+            if numbers.len() != 2 {
+                return Err!(
+                    "Unable to parse {:?}: expected exactly two numbers to resolve into day and month", date);
+            }
+
+            let (first, second) = (numbers[0], numbers[1]);
+
+            if first > 12 && second > 12 {
+                return Err!("Unable to parse {:?}: neither {} nor {} can be a month", date, first, second);
+            } else if first > 12 {
+                (first, second as u32)
+            } else if second > 12 {
+                (second, first as u32)
+            } else if prefer_day_first {
+                (first, second as u32)
+            } else {
+                (second, first as u32)
+            }
+        },
+    };
+
+    NaiveDate::from_ymd_opt(year, month, day as u32).ok_or_else(|| format!(
+        "Unable to parse {:?}: {}-{}-{} is not a valid date", date, year, month, day).into())
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Alpha(String),
+    Numeric(String),
+    Separator,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum CharKind {
+    Alpha,
+    Numeric,
+    Separator,
+}
+
+fn char_kind(char: char) -> CharKind {
+    if char.is_ascii_alphabetic() {
+        CharKind::Alpha
+    } else if char.is_ascii_digit() {
+        CharKind::Numeric
+    } else {
+        CharKind::Separator
+    }
+}
+
+fn tokenize(date: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut current_kind = None;
+
+    for char in date.chars() {
+        let kind = char_kind(char);
+
+        if current_kind.is_some() && current_kind != Some(kind) {
+            tokens.push(make_token(current_kind.unwrap(), &current));
+            current.clear();
+        }
+
+        current_kind = Some(kind);
+        current.push(char);
+    }
+
+    if let Some(kind) = current_kind {
+        tokens.push(make_token(kind, &current));
+    }
+
+    tokens
+}
+
+fn make_token(kind: CharKind, value: &str) -> Token {
+    match kind {
+        CharKind::Alpha => Token::Alpha(value.to_owned()),
+        CharKind::Numeric => Token::Numeric(value.to_owned()),
+        CharKind::Separator => Token::Separator,
+    }
+}
+
+const MONTH_NAMES: &[&str] = &[
+    "january", "february", "march", "april", "may", "june",
+    "july", "august", "september", "october", "november", "december",
+];
+
+fn month_number(word: &str) -> Option<u32> {
+    let word = word.to_lowercase();
+
+    if word.len() < 3 {
+        return None;
+    }
+
+    MONTH_NAMES.iter().position(|name| name.starts_with(&word)).map(|index| (index + 1) as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fuzzy_date_parsing() {
+        assert_eq!(parse_date_fuzzy("2018-06-22", false).unwrap(), date!(22, 6, 2018));
+        assert_eq!(parse_date_fuzzy("22/06/2018", false).unwrap(), date!(22, 6, 2018));
+        assert_eq!(parse_date_fuzzy("06/22/2018", false).unwrap(), date!(22, 6, 2018));
+        assert_eq!(parse_date_fuzzy("03/04/2018", true).unwrap(), date!(3, 4, 2018));
+        assert_eq!(parse_date_fuzzy("03/04/2018", false).unwrap(), date!(4, 3, 2018));
+        assert_eq!(parse_date_fuzzy("October 1, 2018", false).unwrap(), date!(1, 10, 2018));
+        assert_eq!(parse_date_fuzzy("Sep 30 2018", false).unwrap(), date!(30, 9, 2018));
+    }
+
+    #[test]
+    fn fuzzy_date_parsing_errors() {
+        assert!(parse_date_fuzzy("06/22", false).is_err());
+        assert!(parse_date_fuzzy("13/13/2018", false).is_err());
+        assert!(parse_date_fuzzy("Blah 1, 2018", false).is_err());
+    }
+}